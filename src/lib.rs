@@ -1,8 +1,14 @@
 use std::error::Error;
 use rand;
 use rand::Rng;
+use rayon::prelude::*;
 use std::fmt;
+use std::fs;
 use std::collections::HashSet;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
 
 
 type Coord = (i32, i32);
@@ -11,7 +17,14 @@ type Space = HashSet<Coord>;
 struct Config {
     row: usize,
     col: usize,
-    density: f32
+    density: f32,
+    pattern: Option<String>,
+    rule: Rule,
+    threads: usize,
+    history_limit: Option<usize>,
+    seed_interval: usize,
+    seed_population: usize,
+    wrap: bool
 }
 
 impl Config {
@@ -24,15 +37,15 @@ impl Config {
         //Unpack arguments
         let row = match args.next() {
             Some(arg) => match arg.parse::<usize>() {
-                Ok(val) => val,
-                Err(_) => return Err("row must be positive integer")
+                Ok(val) if val > 0 => val,
+                _ => return Err("row must be positive integer")
             },
             None => return Err("not enough arguments")
         };
         let col = match args.next() {
             Some(arg) => match arg.parse::<usize>() {
-                Ok(val) => val,
-                Err(_) => return Err("col must be positive integer")
+                Ok(val) if val > 0 => val,
+                _ => return Err("col must be positive integer")
             },
             None => return Err("not enough arguments")
         };
@@ -44,22 +57,126 @@ impl Config {
             None => return Err("not enough arguments")
         };
 
-        Ok(Config{row, col, density})
+        //Remaining arguments are optional flags, e.g. --pattern <file>, --rule <spec>, --threads <n>
+        let mut pattern = None;
+        let mut rule_spec = None;
+        let mut threads = 1;
+        let mut history_limit = None;
+        let mut seed_interval = 0;
+        let mut seed_population = 0;
+        let mut wrap = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--pattern" => pattern = match args.next() {
+                    Some(path) => Some(path),
+                    None => return Err("--pattern requires a file path")
+                },
+                "--rule" => rule_spec = match args.next() {
+                    Some(spec) => Some(spec),
+                    None => return Err("--rule requires a rule string")
+                },
+                "--threads" => threads = match args.next() {
+                    Some(arg) => match arg.parse::<usize>() {
+                        Ok(val) => val,
+                        Err(_) => return Err("threads must be positive integer")
+                    },
+                    None => return Err("--threads requires a count")
+                },
+                "--history-limit" => history_limit = match args.next() {
+                    Some(arg) => match arg.parse::<usize>() {
+                        Ok(val) => Some(val),
+                        Err(_) => return Err("history-limit must be positive integer")
+                    },
+                    None => return Err("--history-limit requires a count")
+                },
+                "--seed-interval" => seed_interval = match args.next() {
+                    Some(arg) => match arg.parse::<usize>() {
+                        Ok(val) => val,
+                        Err(_) => return Err("seed-interval must be positive integer")
+                    },
+                    None => return Err("--seed-interval requires a count")
+                },
+                "--seed-population" => seed_population = match args.next() {
+                    Some(arg) => match arg.parse::<usize>() {
+                        Ok(val) => val,
+                        Err(_) => return Err("seed-population must be positive integer")
+                    },
+                    None => return Err("--seed-population requires a count")
+                },
+                "--wrap" => wrap = true,
+                _ => return Err("unrecognized argument")
+            }
+        }
+        let rule = match rule_spec {
+            Some(spec) => parse_rule(&spec)?,
+            None => Rule::conway()
+        };
+
+        Ok(Config{row, col, density, pattern, rule, threads, history_limit, seed_interval, seed_population, wrap})
     }
 }
 
+//A B/S rule: the set of live-neighbor counts that birth a dead cell or keep a live one alive
+#[derive(Debug, Clone)]
+struct Rule {
+    birth: HashSet<u8>,
+    survival: HashSet<u8>
+}
+
+impl Rule {
+    fn conway() -> Rule {
+        Rule{birth: [3].iter().cloned().collect(), survival: [2, 3].iter().cloned().collect()}
+    }
+}
+
+//Parse a rule string like "B36/S23" (HighLife) or "B2/S" (Seeds) into birth/survival sets
+fn parse_rule(spec: &str) -> Result<Rule, &'static str> {
+    let mut parts = spec.split('/');
+    let b_part = parts.next().ok_or("rule must be in B.../S... form")?;
+    let s_part = parts.next().ok_or("rule must be in B.../S... form")?;
+    if parts.next().is_some() {
+        return Err("rule must be in B.../S... form");
+    }
+    if !b_part.starts_with('B') || !s_part.starts_with('S') {
+        return Err("rule must be in B.../S... form");
+    }
+
+    let digits = |s: &str| -> Result<HashSet<u8>, &'static str> {
+        s.chars()
+            .map(|c| match c.to_digit(10) {
+                Some(d) if d <= 8 => Ok(d as u8),
+                _ => Err("rule digits must be 0-8")
+            })
+            .collect()
+    };
+
+    Ok(Rule{birth: digits(&b_part[1..])?, survival: digits(&s_part[1..])?})
+}
+
 #[derive(Debug)]
 struct Game {
     space: Space,
     cache: Box<Space>,
     size: (usize, usize),
-    generation: usize
+    generation: usize,
+    rule: Rule,
+    wrap: bool,
+    pool: Option<rayon::ThreadPool>
 }
 
 impl Game {
-    pub fn new(row: usize, col: usize) -> Game {
+    pub fn new(row: usize, col: usize, rule: Rule, threads: usize, wrap: bool) -> Game {
+        //Build the pool once up front; next() reuses it instead of spawning threads every generation
+        let pool = if threads > 1 {
+            Some(rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("failed to build rayon thread pool"))
+        } else {
+            None
+        };
 
-        Game{space: Space::new(), cache: Box::new(Space::new()), size: (row, col), generation: 0}
+        Game{space: Space::new(), cache: Box::new(Space::new()), size: (row, col), generation: 0, rule, wrap, pool}
     }
 
     fn init(&mut self, density: f32) -> Result<(), &'static str> {
@@ -80,11 +197,59 @@ impl Game {
         Ok(())
     }
 
+    fn reseed(&mut self, population: usize) {
+        //Top up the board with up to `population` freshly-living cells
+        let capacity = self.size.0 * self.size.1;
+        let target = self.alive() + population.min(capacity.saturating_sub(self.alive()));
+        while self.alive() < target {
+            let coord = (rand::thread_rng().gen_range::<usize>(0, self.size.0) as i32,
+                rand::thread_rng().gen_range::<usize>(0, self.size.1) as i32);
+            if !self.cell_state(coord) {
+                self.set_cell_state(coord);
+                self.insert_cache(&self.neighbors(coord));
+            }
+        }
+    }
+
+    fn load_pattern(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let cells = if path.ends_with(".rle") {
+            parse_rle(&contents)?
+        } else {
+            parse_plaintext(&contents)
+        };
+
+        let (width, height) = pattern_bounds(&cells);
+        let row_offset = (self.size.0 as i32 - height) / 2;
+        let col_offset = (self.size.1 as i32 - width) / 2;
+
+        for (row, col) in cells {
+            let coord = (row + row_offset, col + col_offset);
+            if coord.0 >= 0 && coord.1 >= 0
+                && (coord.0 as usize) < self.size.0 && (coord.1 as usize) < self.size.1
+            {
+                self.set_cell_state(coord);
+                self.insert_cache(&self.neighbors(coord));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn alive(&self) -> usize {
         //Get count of living cells
         self.space.len()
     }
 
+    pub fn state_hash(&self) -> u64 {
+        //Stable hash of the current space, used to detect when a pattern repeats
+        let mut cells: Vec<&Coord> = self.space.iter().collect();
+        cells.sort();
+        let mut hasher = DefaultHasher::new();
+        cells.hash(&mut hasher);
+        hasher.finish()
+    }
+
     pub fn generation(&self) -> usize {
         //Get count of generations passed
         self.generation
@@ -113,64 +278,81 @@ impl Game {
 
     fn neighbors(&self, coord: Coord) -> [Coord; 8] {
         let (x, y) = coord;
-        [
-         (x + 1, y),
-         (x - 1, y),
-         (x, y + 1),
-         (x, y - 1),
-         (x + 1, y + 1),
-         (x + 1, y - 1),
-         (x - 1, y + 1),
-         (x - 1, y - 1)
-        ]
+        if self.wrap {
+            let rows = self.size.0 as i32;
+            let cols = self.size.1 as i32;
+            let wrap_row = |v: i32| (v % rows + rows) % rows;
+            let wrap_col = |v: i32| (v % cols + cols) % cols;
+            [
+             (wrap_row(x + 1), wrap_col(y)),
+             (wrap_row(x - 1), wrap_col(y)),
+             (wrap_row(x), wrap_col(y + 1)),
+             (wrap_row(x), wrap_col(y - 1)),
+             (wrap_row(x + 1), wrap_col(y + 1)),
+             (wrap_row(x + 1), wrap_col(y - 1)),
+             (wrap_row(x - 1), wrap_col(y + 1)),
+             (wrap_row(x - 1), wrap_col(y - 1))
+            ]
+        } else {
+            [
+             (x + 1, y),
+             (x - 1, y),
+             (x, y + 1),
+             (x, y - 1),
+             (x + 1, y + 1),
+             (x + 1, y - 1),
+             (x - 1, y + 1),
+             (x - 1, y - 1)
+            ]
+        }
     }
 
     fn next_cell_state(&self, coord: Coord) -> (bool, Option<[Coord; 8]>) {
         //Check cell's neighbors to determine next state
         //Return 1. Cell's next state
-        //       2. Cell's neighbors (if state changed)
+        //       2. Cell's neighbors (if state changed, so the cache stays primed)
         let neighbors = self.neighbors(coord);
-        let curr_cell = if self.cell_state(coord) {
-            1
+        let alive = self.cell_state(coord);
+        let live_neighbors = neighbors.iter()
+            .map(|c| self.cell_state(*c))
+            .filter(|c| *c)
+            .collect::<Vec<_>>()
+            .len() as u8;
+
+        let next_alive = if alive {
+            self.rule.survival.contains(&live_neighbors)
         } else {
-            0
+            self.rule.birth.contains(&live_neighbors)
         };
-        match neighbors.iter()
-                .map(|c| self.cell_state(*c))
-                .filter(|c| *c)
-                .collect::<Vec<_>>()
-                .len() + curr_cell
-        {
-            3 => {
-                if 0 == curr_cell {
-                    //Cell revived, need to check neighbors
-                    return (true, Some(neighbors));
-                }
-                (true, None)
-            },
-            4 => (1 == curr_cell, None),
-            _ => {
-                if 1 == curr_cell {
-                    //Cell died, need to check neighbors
-                    return (false, Some(neighbors));
-                }
-                (false, None)
-            }
+
+        if next_alive != alive {
+            (next_alive, Some(neighbors))
+        } else {
+            (next_alive, None)
         }
     }
 
     fn next(&mut self) {
         //Calculate next generation of Game
         let cache = self.cache.clone();
-        let mut next_space = Space::new();
         self.cache = Box::new(Space::new());
 
-        for coord in cache.iter() {
-            let next_state = self.next_cell_state(*coord);
+        let results = if let Some(pool) = &self.pool {
+            //next_cell_state only reads self.space, so evaluating the cache in parallel is safe
+            let coords: Vec<Coord> = cache.iter().cloned().collect();
+            pool.install(|| {
+                coords.par_iter().map(|coord| (*coord, self.next_cell_state(*coord))).collect::<Vec<_>>()
+            })
+        } else {
+            cache.iter().map(|coord| (*coord, self.next_cell_state(*coord))).collect::<Vec<_>>()
+        };
+
+        let mut next_space = Space::new();
+        for (coord, next_state) in results {
             if next_state.0 {
                 //Add cell to space and cache if living
-                self.insert_cache(&[*coord]);
-                next_space.insert(*coord);
+                self.insert_cache(&[coord]);
+                next_space.insert(coord);
             }
             if let Some(neighbors) = next_state.1 {
                 //Cache cell's neighbors if cell's state changed
@@ -183,6 +365,71 @@ impl Game {
     }
 }
 
+//Parse a plaintext (.cells) pattern: '.' is dead, 'O'/'*' is alive, one char per column
+fn parse_plaintext(contents: &str) -> Vec<Coord> {
+    let mut cells = Vec::new();
+    for (row, line) in contents.lines().filter(|l| !l.starts_with('!')).enumerate() {
+        for (col, ch) in line.chars().enumerate() {
+            if ch == 'O' || ch == '*' {
+                cells.push((row as i32, col as i32));
+            }
+        }
+    }
+    cells
+}
+
+//Parse an RLE pattern: 'b' dead, 'o' alive, '$' end-of-row, '!' end-of-pattern,
+//an optional leading integer multiplies the following tag
+fn parse_rle(contents: &str) -> Result<Vec<Coord>, &'static str> {
+    let mut cells = Vec::new();
+    let mut row: i32 = 0;
+    let mut col: i32 = 0;
+    let mut count = String::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('x') {
+            //Comment or header line (e.g. "x = 3, y = 3, rule = B3/S23")
+            continue;
+        }
+
+        for ch in line.chars() {
+            match ch {
+                '0'..='9' => count.push(ch),
+                'b' | 'o' => {
+                    let run = count.parse::<i32>().unwrap_or(1);
+                    count.clear();
+                    if ch == 'o' {
+                        for _ in 0..run {
+                            cells.push((row, col));
+                            col += 1;
+                        }
+                    } else {
+                        col += run;
+                    }
+                },
+                '$' => {
+                    let run = count.parse::<i32>().unwrap_or(1);
+                    count.clear();
+                    row += run;
+                    col = 0;
+                },
+                '!' => return Ok(cells),
+                _ => return Err("unrecognized token in rle pattern")
+            }
+        }
+    }
+
+    Ok(cells)
+}
+
+//Width and height of a pattern's bounding box, derived from its live cells
+fn pattern_bounds(cells: &[Coord]) -> (i32, i32) {
+    let height = cells.iter().map(|c| c.0).max().map_or(0, |m| m + 1);
+    let width = cells.iter().map(|c| c.1).max().map_or(0, |m| m + 1);
+    (width, height)
+}
+
 impl fmt::Display for Game {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Rust Life:\n\tGeneration {}\n\tAlive {}\n\n", self.generation(), self.alive())?;
@@ -206,14 +453,46 @@ pub fn run<T>(args: T) -> Result<(), Box<dyn Error>>
     where T: Iterator<Item = String>
 {
     let config = Config::new(args)?;
-    let mut game = Game::new(config.row, config.col);
-    game.init(config.density)?;
+    let mut game = Game::new(config.row, config.col, config.rule, config.threads, config.wrap);
+    match &config.pattern {
+        Some(path) => game.load_pattern(path)?,
+        None => game.init(config.density)?
+    }
+
+    let mut history: HashMap<u64, usize> = HashMap::new();
+    let mut history_order: VecDeque<u64> = VecDeque::new();
+    let initial_hash = game.state_hash();
+    history.insert(initial_hash, game.generation());
+    history_order.push_back(initial_hash);
 
     while game.generation() < 1_000_000 && game.alive() > 0 {
         println!("{}{}", "\n".repeat(config.row+4), game);
         std::thread::sleep(std::time::Duration::from_millis(50));
         //println!("{} -- {}", game.generation(), game.alive());
         game.next();
+
+        if config.seed_interval > 0
+            && (game.alive() == 0 || game.generation().is_multiple_of(config.seed_interval))
+        {
+            game.reseed(config.seed_population);
+        }
+
+        let hash = game.state_hash();
+        if let Some(&first_seen) = history.get(&hash) {
+            let period = game.generation() - first_seen;
+            println!("Pattern repeated after {} generations -- {}", period,
+                if period == 1 { "still life" } else { "oscillator" });
+            break;
+        }
+        history.insert(hash, game.generation());
+        history_order.push_back(hash);
+        if let Some(limit) = config.history_limit {
+            while history.len() > limit {
+                if let Some(oldest) = history_order.pop_front() {
+                    history.remove(&oldest);
+                }
+            }
+        }
     }
 
     println!("{}{}", "\n".repeat(config.row+4), game);
@@ -268,12 +547,146 @@ mod tests {
             "no cells should be living");
     }
 
+    #[test]
+    fn parse_plaintext_reads_alive_cells() {
+        let contents = "!Name: test\n.O.\nOOO\n...";
+        let mut cells = parse_plaintext(contents);
+        cells.sort();
+        assert_eq!(cells, vec![(0, 1), (1, 0), (1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn parse_rle_decodes_a_glider() {
+        let contents = "x = 3, y = 3, rule = B3/S23\nbo$\n2bo$\n3o!";
+        let cells = parse_rle(contents).expect("valid rle");
+        assert_eq!(cells, vec![(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn parse_rle_run_count_persists_across_lines() {
+        //The run count for the trailing "o" tag is written on its own line
+        let contents = "x = 2, y = 1, rule = B3/S23\n2\no!";
+        let cells = parse_rle(contents).expect("valid rle");
+        assert_eq!(cells, vec![(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn pattern_bounds_computes_bounding_box() {
+        let cells = vec![(0, 1), (2, 3), (1, 0)];
+        assert_eq!(pattern_bounds(&cells), (4, 3));
+    }
+
+    #[test]
+    fn load_pattern_rejects_cells_outside_board() {
+        let path = "/tmp/rust_life_test_load_pattern_oob.cells";
+        //Pattern is wider than the 3x3 board it's centered on; the outermost column on
+        //each side falls outside [0, 3) once centered and should be dropped
+        fs::write(path, "OOOOO").expect("write temp pattern");
+
+        let mut game = Game::new(3, 3, Rule::conway(), 1, false);
+        game.load_pattern(path).expect("load pattern");
+        fs::remove_file(path).ok();
+
+        assert_eq!(game.alive(), 3);
+        assert!(game.cell_state((1, 0)));
+        assert!(game.cell_state((1, 1)));
+        assert!(game.cell_state((1, 2)));
+    }
+
+    #[test]
+    fn blinker_oscillates_with_period_two() {
+        let mut game = Game::new(5, 5, Rule::conway(), 1, false);
+        let living_cells = vec![(2, 1), (2, 2), (2, 3)];
+        prime_test_cache(&mut game, living_cells);
+
+        let mut history: HashMap<u64, usize> = HashMap::new();
+        history.insert(game.state_hash(), game.generation());
+
+        let period = loop {
+            game.next();
+            let hash = game.state_hash();
+            if let Some(&first_seen) = history.get(&hash) {
+                break game.generation() - first_seen;
+            }
+            history.insert(hash, game.generation());
+        };
+
+        assert_eq!(period, 2, "a blinker should repeat its starting state every 2 generations");
+    }
+
+    #[test]
+    fn reseed_adds_population_without_disturbing_existing_cells() {
+        let mut game = Game::new(10, 10, Rule::conway(), 1, false);
+        let living_cells = vec![(0, 0), (1, 1)];
+        prime_test_cache(&mut game, living_cells.clone());
+
+        game.reseed(5);
+
+        assert_eq!(game.alive(), living_cells.len() + 5);
+        for cell in &living_cells {
+            assert!(game.cell_state(*cell), "existing live cell should remain alive");
+        }
+    }
+
+    #[test]
+    fn parse_rule_rejects_out_of_range_digit() {
+        assert!(parse_rule("B9/S23").is_err());
+    }
+
+    #[test]
+    fn highlife_rule_births_on_six_neighbors_unlike_conway() {
+        //Two rows of 3 live cells, above and below (1, 1), give it 6 live neighbors -- Conway keeps it dead, HighLife (B36/S23) revives it
+        let rule = parse_rule("B36/S23").expect("valid rule");
+        let mut game = Game::new(5, 5, rule, 1, false);
+        let living_cells = vec![(0, 0), (0, 1), (0, 2), (2, 0), (2, 1), (2, 2)];
+        prime_test_cache(&mut game, living_cells);
+
+        assert!(!game.cell_state((1, 1)));
+        game.next();
+        assert!(game.cell_state((1, 1)), "HighLife should birth a cell with 6 live neighbors");
+    }
+
+    #[test]
+    fn parallel_and_serial_produce_same_next_generation() {
+        let living_cells = vec![(0, 0), (0, 1), (1, 0), (1, 1), (4, 4)];
+
+        let mut serial = Game::new(10, 10, Rule::conway(), 1, false);
+        prime_test_cache(&mut serial, living_cells.clone());
+        serial.next();
+
+        let mut parallel = Game::new(10, 10, Rule::conway(), 2, false);
+        prime_test_cache(&mut parallel, living_cells);
+        parallel.next();
+
+        assert_eq!(serial.space, parallel.space);
+    }
+
+    #[test]
+    fn wrap_neighbors_wrap_around_edges() {
+        let game = Game::new(3, 3, Rule::conway(), 1, true);
+        let mut neighbors = game.neighbors((0, 0));
+        neighbors.sort();
+
+        let mut expected = [
+            (1, 0), (2, 0), (0, 1), (0, 2),
+            (1, 1), (1, 2), (2, 1), (2, 2)
+        ];
+        expected.sort();
+        assert_eq!(neighbors, expected);
+    }
+
+    #[test]
+    fn reject_zero_size_config() {
+        let args = vec!["prog".to_string(), "0".to_string(), "10".to_string(), "0.5".to_string()];
+        assert!(Config::new(args.into_iter()).is_err());
+    }
+
     fn new_test_game(row: usize, col: usize) -> Game {
-        Game::new(row, col)
+        Game::new(row, col, Rule::conway(), 1, false)
     }
 
     fn init_test_game(row: usize, col: usize, density: f32) -> Game {
-        let mut game = Game::new(row, col);
+        let mut game = Game::new(row, col, Rule::conway(), 1, false);
         if let Err(e) = game.init(density) {
             panic!("{}", e);
         };